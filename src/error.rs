@@ -1,11 +1,22 @@
 //! Module containing code for various errors that may need to be handled.
 
 use std::error::Error;
-use std::fmt::{Debug, Display, Formatter, Write};
+use std::fmt::{Debug, Display, Formatter};
 
 pub enum MapErrorKind {
     AllocationError,
-    AccessError
+    AccessError,
+
+    /// The file backing a `PersistentIndexMap` didn't start with the expected magic bytes, or its
+    /// length doesn't match any valid bucket table size.
+    WrongMagic,
+
+    /// The file backing a `PersistentIndexMap` was written by an unsupported format version.
+    UnsupportedVersion(u8),
+
+    /// The entry count recorded in a `PersistentIndexMap`'s header didn't match the number of
+    /// occupied buckets actually found in the file.
+    WrongEntryCount { header: u64, actual: u64 },
 }
 
 /// A struct handling error reporting for the `CIndexMap` type.
@@ -13,8 +24,8 @@ pub enum MapErrorKind {
 /// This error contains the kind of error that the map ran into, and the message to display
 /// when displaying the error.
 pub struct CIndexMapError {
-    /// A static string containing the message associated with the error.
-    message: &'static str,
+    /// The message associated with the error.
+    message: String,
 
     /// A `MapErrorKind`, containing the type of error that the map encountered.
     kind: MapErrorKind
@@ -26,24 +37,29 @@ impl CIndexMapError {
     /// This is only used within `x-map`, and cannot be called externally.
     pub(in crate) fn new(
         kind: MapErrorKind,
-        message: &'static str
+        message: impl Into<String>
     ) -> CIndexMapError {
         CIndexMapError {
             kind,
-            message
+            message: message.into()
         }
     }
+
+    /// Returns the `MapErrorKind` this error was constructed with.
+    pub fn kind(&self) -> &MapErrorKind {
+        &self.kind
+    }
 }
 
 impl Debug for CIndexMapError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.message)
+        f.write_str(&self.message)
     }
 }
 
 impl Display for CIndexMapError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.message)
+        f.write_str(&self.message)
     }
 }
 
@@ -1,5 +1,15 @@
 //! `x-map` is a crate intended to add some more map and vector implementations which are fast,
 //! and flexible in their usage environments.
+//!
+//! Maps that manage their own raw allocations (`CIndexMap`, `CHashMap`) are generic over
+//! `core::alloc::Allocator`, so a custom allocator can be supplied via their `new_in` constructor
+//! in environments where the global allocator isn't available. The `std` feature additionally
+//! enables the `Global`-backed `new()` convenience constructors.
+//!
+//! That allocator parameterization doesn't make the crate `#![no_std]`-compatible on its own -
+//! see the `maps` module docs for what else still depends on `std`.
+
+#![feature(allocator_api)]
 
 pub mod maps;
 pub mod error;
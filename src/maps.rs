@@ -4,121 +4,218 @@
 //! Many map types in this module utilize raw pointers. This unsafe code is managed internally by each
 //! map contained within, and this unsafe code should be abstracted away from library users.
 //!
-//! To support multiple environments, many maps will also contain functions for low-level operations,
-//! so that in environments such as `#![no_std]` environments, these maps are still usable.
+//! `CIndexMap` and `CHashMap` are generic over `core::alloc::Allocator`, so their own
+//! allocation/reallocation/deallocation can be routed through a custom allocator instead of the
+//! global one via `new_in`. That parameterization is the extent of this crate's `no_std` support
+//! today: `CHashMap::hash_of` hashes with `std::collections::hash_map::DefaultHasher`,
+//! `CIndexMapError` unconditionally implements `std::error::Error`, and `PersistentIndexMap`
+//! memory-maps an OS file - all of which still require `std`.
 //!
 //! As a rule of thumb, all functions in which it is expected to retrieve a value will retrieve a
 //! reference to that value, not a copy of it.
 
 use crate::error::{CIndexMapError, MapErrorKind};
-use crate::util::mem_cmp;
-use core::alloc::Layout;
+use crate::util::{clear_bit, get_bit, mask_bytes, mem_cmp, set_bit};
+use core::alloc::{Allocator, Layout};
 use core::fmt::Debug;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
 use core::ptr;
+use core::ptr::NonNull;
+use memmap2::MmapMut;
+use std::alloc::Global;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
 
 #[derive(Debug)]
-pub struct CIndexMap<K, V> {
-    /// The initial layout used to initialize this map's keys store.
+pub struct CIndexMap<K, V, A: Allocator = Global> {
+    /// The layout used for the current `keys` allocation.
     key_layout: Layout,
 
-    /// The initial layout used to initialize this map's values store.
+    /// The layout used for the current `values` allocation.
     val_layout: Layout,
 
+    /// The layout used for the current `init_mask` allocation.
+    mask_layout: Layout,
+
     /// A pointer to a space in memory in which keys will be stored at.
     keys: *mut K,
 
     /// A pointer to a space in memory in which values will be stored at.
     values: *mut V,
 
-    /// The total allocated memory for this map.
+    /// A packed, one-bit-per-slot mask tracking which of `0..self.cap` currently hold an
+    /// initialized key/value pair. A set bit means `keys[i]`/`values[i]` are initialized; a
+    /// clear bit means that slot is uninitialized, whether because it was never written
+    /// (e.g. after `reserve`) or because it held a value that was `remove`d.
     ///
-    /// Everything in the range `self.pos + 1..self.cap` is not uninitialized, but may be old
-    /// or unwanted.
+    /// This is what makes sparse and pre-reserved usage sound: nothing about this map requires
+    /// the initialized slots to form a contiguous `0..=pos` run any more.
+    init_mask: *mut u8,
+
+    /// The total allocated capacity, in slots, for this map. Slots past `self.cap` don't exist
+    /// yet; slots before it may or may not be initialized, per `self.init_mask`.
     cap: isize,
 
-    /// Position of the cursor where the **last valid value** was inserted. `keys[pos]` and
-    /// `values[pos]` will always be initialized with valid data.
-    ///
-    /// All elements 0 to `self.pos` are guaranteed to be initialized.
+    /// The highest index any slot may be live at: `insert`/`insert_at` raise it to the slot they
+    /// write, and `remove` lowers it by one as it shifts later slots down. This only bounds how far `get`,
+    /// `index`, and similar lookups need to scan - whether a given slot in `0..=self.pos` is
+    /// actually live is determined by `self.init_mask`, not by this cursor.
     pos: isize,
+
+    /// The allocator backing this map's `keys`/`values`/`init_mask` storage.
+    ///
+    /// Supply a custom allocator via `new_in` to route this storage through something other
+    /// than the global allocator (e.g. a bump/arena allocator).
+    alloc: A,
+}
+
+impl<K, V> CIndexMap<K, V, Global> {
+    /// Constructs a new `CIndexMap` backed by the global allocator.
+    ///
+    /// # Panics
+    /// When `size_of::<K>` or `size_of::<V>` is 0.
+    #[cfg(feature = "std")]
+    pub fn new() -> CIndexMap<K, V, Global> {
+        CIndexMap::new_in(Global)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> Default for CIndexMap<K, V, Global> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<K, V> CIndexMap<K, V> {
-    /// Constructs a new `CIndexMap`.
+impl<K, V, A: Allocator> CIndexMap<K, V, A> {
+    /// Constructs a new `CIndexMap` backed by the given allocator `alloc`.
+    ///
+    /// This routes every allocation through `A` instead of assuming the global allocator is
+    /// available.
     ///
     /// # Panics
     /// When `size_of::<K>` or `size_of::<T>` is 0.
-    pub fn new() -> CIndexMap<K, V> {
+    pub fn new_in(alloc: A) -> CIndexMap<K, V, A> {
         if size_of::<K>() == 0 || size_of::<V>() == 0 {
             panic!("Cannot initialize CIndexMap with ZSTs!");
         }
 
-        let key_layout = unsafe {
-            Layout::from_size_align(size_of::<K>() * 1, align_of::<K>()).unwrap()
-        };
-        let val_layout = unsafe {
-            Layout::from_size_align(size_of::<V>() * 1, align_of::<V>()).unwrap()
-        };
+        let key_layout = Layout::from_size_align(size_of::<K>(), align_of::<K>()).unwrap();
+        let val_layout = Layout::from_size_align(size_of::<V>(), align_of::<V>()).unwrap();
+        let mask_layout = Layout::array::<u8>(mask_bytes(1)).unwrap();
 
         // SAFETY:
         // ZSTs are not supported, this point in the initializing cannot be reached
         // if a ZST is provided.
-        let key_ptr = unsafe {
-            std::alloc::alloc(key_layout)
-        } as *mut K;
+        let key_ptr = alloc.allocate(key_layout).expect("allocation failure").as_ptr() as *mut K;
 
         // SAFETY:
         // ZSTs are not supported, this point in the initializing cannot be reached
         // if a ZST is provided.
-        let val_ptr = unsafe {
-            std::alloc::alloc(val_layout)
-        } as *mut V;
+        let val_ptr = alloc.allocate(val_layout).expect("allocation failure").as_ptr() as *mut V;
+
+        // SAFETY: a zeroed mask is a fully-initialized mask - every bit clear means every slot
+        // reads as uninitialized, which is correct for a freshly allocated map.
+        let mask_ptr = alloc.allocate_zeroed(mask_layout).expect("allocation failure").as_ptr() as *mut u8;
 
         CIndexMap {
             key_layout,
             val_layout,
+            mask_layout,
             keys: key_ptr,
             values: val_ptr,
+            init_mask: mask_ptr,
             cap: 1,
             pos: -1,
+            alloc,
         }
     }
 
+    /// Grows `keys`/`values`/`init_mask` so that `self.cap >= min_cap`, leaving any newly
+    /// added slots uninitialized (per `init_mask`).
+    ///
+    /// Growth is amortized doubling, so the number of reallocations stays logarithmic in the
+    /// number of slots ever requested, instead of reallocating on every bit of growth.
+    fn ensure_capacity(&mut self, min_cap: isize) -> crate::result::Result<()> {
+        if min_cap <= self.cap {
+            return Ok(());
+        }
+
+        let mut new_cap = self.cap.max(1);
+        while new_cap < min_cap {
+            new_cap *= 2;
+        }
+
+        let new_key_layout = Layout::from_size_align(
+            size_of::<K>() * (new_cap as usize),
+            align_of::<K>(),
+        ).unwrap();
+        let new_val_layout = Layout::from_size_align(
+            size_of::<V>() * (new_cap as usize),
+            align_of::<V>(),
+        ).unwrap();
+        let new_mask_layout = Layout::array::<u8>(mask_bytes(new_cap as usize)).unwrap();
+
+        // `A::grow`/`grow_zeroed` frees (or moves) the old block as soon as it returns `Ok`, so
+        // each pointer/layout pair is committed to `self` the moment its own grow succeeds,
+        // rather than held in a local and discarded on the error path - otherwise a later grow
+        // failing in this same call would leave an earlier-succeeded field pointing at memory
+        // the allocator already reclaimed.
+
+        // SAFETY: `self.keys`/`self.key_layout` describe the allocation currently being grown.
+        let new_key_ptr = unsafe {
+            self.alloc.grow(
+                NonNull::new(self.keys as *mut u8).unwrap(),
+                self.key_layout,
+                new_key_layout,
+            )
+        }.map_err(|_| CIndexMapError::new(
+            MapErrorKind::AllocationError,
+            "Error when attempting to allocate map memory."
+        ))?;
+        self.keys = new_key_ptr.as_ptr() as *mut K;
+        self.key_layout = new_key_layout;
+
+        // SAFETY: `self.values`/`self.val_layout` describe the allocation currently being grown.
+        let new_val_ptr = unsafe {
+            self.alloc.grow(
+                NonNull::new(self.values as *mut u8).unwrap(),
+                self.val_layout,
+                new_val_layout,
+            )
+        }.map_err(|_| CIndexMapError::new(
+            MapErrorKind::AllocationError,
+            "Error when attempting to allocate map memory."
+        ))?;
+        self.values = new_val_ptr.as_ptr() as *mut V;
+        self.val_layout = new_val_layout;
+
+        // SAFETY: `grow_zeroed` guarantees the bytes beyond the old mask's length are zeroed,
+        // so the newly added capacity reads as uninitialized without a separate clearing pass.
+        let new_mask_ptr = unsafe {
+            self.alloc.grow_zeroed(
+                NonNull::new(self.init_mask).unwrap(),
+                self.mask_layout,
+                new_mask_layout,
+            )
+        }.map_err(|_| CIndexMapError::new(
+            MapErrorKind::AllocationError,
+            "Error when attempting to allocate map memory."
+        ))?;
+        self.init_mask = new_mask_ptr.as_ptr() as *mut u8;
+        self.mask_layout = new_mask_layout;
+
+        self.cap = new_cap;
+        Ok(())
+    }
+
     /// Inserts a new key/value pair into the map.
     ///
     /// This function returns `Ok(())` if the key/value pair was inserted successfully.
     pub fn insert(&mut self, key: K, value: V) -> crate::result::Result<()> {
-        if (self.pos + 1) >= self.cap {
-            let new_cap = self.cap + 2;
-
-            let new_key_ptr = unsafe {
-                std::alloc::realloc(
-                    self.keys as *mut u8,
-                    self.key_layout,
-                    size_of::<K>() * (new_cap as usize),
-                ) as *mut K
-            };
-
-            let new_val_ptr = unsafe {
-                std::alloc::realloc(
-                    self.values as *mut u8,
-                    self.val_layout,
-                    size_of::<V>() * (new_cap as usize),
-                ) as *mut V
-            };
-
-            if (new_val_ptr == ptr::null_mut()) || (new_key_ptr == ptr::null_mut()) {
-                return Err(CIndexMapError::new(
-                    MapErrorKind::AllocationError,
-                    "Error when attempting to allocate map memory."
-                ));
-            } else {
-                self.keys = new_key_ptr;
-                self.values = new_val_ptr;
-                self.cap = new_cap;
-            }
-        }
-
+        self.ensure_capacity(self.pos + 2)?;
         self.pos += 1;
 
         // SAFETY:
@@ -128,44 +225,94 @@ impl<K, V> CIndexMap<K, V> {
         unsafe {
             self.keys.offset(self.pos).write(key);
             self.values.offset(self.pos).write(value);
+            set_bit(self.init_mask, self.pos as usize);
+        }
+
+        Ok(())
+    }
+
+    /// Grows the map's capacity by at least `additional` slots without initializing any of
+    /// them.
+    ///
+    /// Unlike `insert`, this never writes a key or value - it's meant to be paired with
+    /// `insert_at` to fill arbitrary positions, or to let a run of later `insert` calls append
+    /// without reallocating.
+    pub fn reserve(&mut self, additional: usize) -> crate::result::Result<()> {
+        self.ensure_capacity(self.cap + additional as isize)
+    }
+
+    /// Inserts a key/value pair at an arbitrary `index`, growing capacity as needed.
+    ///
+    /// Unlike `insert`, which always appends after the last written slot, `insert_at` can fill
+    /// any position - including ones left uninitialized by `reserve`. If `index` already held a
+    /// value, the old key/value are dropped before being overwritten.
+    pub fn insert_at(&mut self, index: usize, key: K, value: V) -> crate::result::Result<()> {
+        self.ensure_capacity(index as isize + 1)?;
+
+        // SAFETY: `ensure_capacity` guarantees `index` is within the allocated range, and
+        // `init_mask` tells us whether `index` currently holds a value to drop first.
+        unsafe {
+            if get_bit(self.init_mask, index) {
+                ptr::drop_in_place(self.keys.add(index));
+                ptr::drop_in_place(self.values.add(index));
+            }
+
+            self.keys.add(index).write(key);
+            self.values.add(index).write(value);
+            set_bit(self.init_mask, index);
         }
 
+        self.pos = self.pos.max(index as isize);
+
         Ok(())
     }
 
     /// Removes an element at the specified index.
     pub fn remove(&mut self, index: usize) -> crate::result::Result<()> {
         if index > (self.pos as usize) {
-            Err(
-                CIndexMapError::new(
-                    MapErrorKind::AccessError,
-                    "Attempted to access a map index that surpasses the bounds of the current map."
-                )
-            )
-        } else {
-            // SAFETY:
-            // We've determined that the index requested fits within the range of valid data.
-            unsafe {
-                // Shift all elements between `index` and `self.pos` back by one by copying
-                // the pointer and overwriting it at the position specified by `index`.
-                ptr::copy(
-                    self.keys.offset((index as isize) + 1),
-                    self.keys.offset(index as isize),
-                    (self.pos as usize - index),
-                );
-
-                ptr::copy(
-                    self.values.offset((index as isize) + 1),
-                    self.values.offset(index as isize),
-                    self.pos as usize - index,
-                );
-            }
+            return Err(CIndexMapError::new(
+                MapErrorKind::AccessError,
+                "Attempted to access a map index that surpasses the bounds of the current map."
+            ));
+        }
+
+        // SAFETY: bounds were checked above; an empty map (`self.pos == -1`) is rejected before
+        // the mask is read.
+        if self.pos < 0 || unsafe { !get_bit(self.init_mask, index) } {
+            return Err(CIndexMapError::new(
+                MapErrorKind::AccessError,
+                "Attempted to remove a map index that has not been initialized."
+            ));
+        }
+
+        // SAFETY:
+        // We've determined that the index requested fits within the range of valid data.
+        unsafe {
+            // Drop the element being removed before it's overwritten by the shift below;
+            // `ptr::copy` would otherwise silently leak whatever `K`/`V` currently live here.
+            ptr::drop_in_place(self.keys.add(index));
+            ptr::drop_in_place(self.values.add(index));
 
-            // Decrement self.pos to ensure this is always set as the position of the
-            // last valid inserted value.
-            self.pos -= 1;
-            Ok(())
+            // Shift each live slot between `index` and `self.pos` back by one. `insert_at`/
+            // `reserve` can leave holes in this range, so - unlike a single bulk `ptr::copy`
+            // over the whole range - this only touches slots `init_mask` marks live; copying an
+            // uninitialized `K`/`V` (e.g. a `String`) out of a hole would read uninitialized
+            // memory.
+            for i in (index + 1)..=(self.pos as usize) {
+                if get_bit(self.init_mask, i) {
+                    ptr::copy_nonoverlapping(self.keys.add(i), self.keys.add(i - 1), 1);
+                    ptr::copy_nonoverlapping(self.values.add(i), self.values.add(i - 1), 1);
+                    set_bit(self.init_mask, i - 1);
+                } else {
+                    clear_bit(self.init_mask, i - 1);
+                }
+            }
+            clear_bit(self.init_mask, self.pos as usize);
         }
+
+        // Decrement self.pos, since every slot after `index` moved down by one.
+        self.pos -= 1;
+        Ok(())
     }
 
     /// Returns the key to the entry at the specified index.
@@ -173,20 +320,27 @@ impl<K, V> CIndexMap<K, V> {
     /// Use `CIndexMap::get` to get the value of the entry based off of the key.
     pub fn index(&self, index: usize) -> crate::result::Result<&K> {
         if index > (self.pos as usize) {
-            Err(
-                CIndexMapError::new(
-                    MapErrorKind::AccessError,
-                    "Attempted to access a map index that surpasses the bounds of the current map."
-                )
+            return Err(CIndexMapError::new(
+                MapErrorKind::AccessError,
+                "Attempted to access a map index that surpasses the bounds of the current map."
+            ));
+        }
+
+        // SAFETY: bounds were checked above; an empty map (`self.pos == -1`) is rejected before
+        // the mask is read.
+        if self.pos < 0 || unsafe { !get_bit(self.init_mask, index) } {
+            return Err(CIndexMapError::new(
+                MapErrorKind::AccessError,
+                "Attempted to access a map index that has not been initialized."
+            ));
+        }
+
+        // SAFETY:
+        // `init_mask` confirmed this slot holds an initialized key.
+        unsafe {
+            Ok(
+                &*self.keys.add(index)
             )
-        } else {
-            // SAFETY:
-            // All elements `0..self.pos` should be already be initialized.
-            unsafe {
-                Ok(
-                    &*self.keys.offset(index as isize)
-                )
-            }
         }
     }
 
@@ -233,18 +387,20 @@ impl<K, V> CIndexMap<K, V> {
         let mut i = 0;
 
         // SAFETY:
-        // We only iterate the memory space between 0 and self.pos, which is always initialized.
+        // We only iterate the memory space between 0 and self.pos, skipping slots `init_mask`
+        // marks uninitialized.
         //
         // The type system guarantees that the supplied `key`, the position of the pointer, and the
         // `size_of::<K>` are all already valid. This means `mem_cmp` can safely be called,
-        // as the data in `self.keys[0..self.pos]` is already initialized, and is valid for both
+        // as a set mask bit guarantees `self.keys[i]` is initialized, and is valid for both
         // the size of `key`, and the size of type `K`.
         unsafe {
             while i <= self.pos {
-                let cmp = mem_cmp(key_ptr as *const u8, self.keys.offset(i) as *const u8, size_of::<K>());
-                match cmp {
-                    None => { return Some(&*self.values.offset(i)); }
-                    Some(_) => {}
+                if get_bit(self.init_mask, i as usize) {
+                    let cmp = mem_cmp(key_ptr as *const u8, self.keys.offset(i) as *const u8, size_of::<K>());
+                    if cmp.is_none() {
+                        return Some(&*self.values.offset(i));
+                    }
                 }
 
                 i += 1;
@@ -255,7 +411,30 @@ impl<K, V> CIndexMap<K, V> {
     }
 }
 
-impl<K: PartialEq, V> CIndexMap<K, V> {
+impl<K, V, A: Allocator> Drop for CIndexMap<K, V, A> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // A set bit in `init_mask` guarantees the corresponding `keys`/`values` slot is
+        // initialized; unset slots (e.g. reserved-but-unfilled, or previously `remove`d) must
+        // not be dropped. `self.keys`/`self.values`/`self.init_mask` were allocated with
+        // `self.key_layout`/`self.val_layout`/`self.mask_layout`, which are kept in sync with
+        // the current capacity by `ensure_capacity`, so deallocating with them here is sound.
+        unsafe {
+            for i in 0..=self.pos {
+                if get_bit(self.init_mask, i as usize) {
+                    ptr::drop_in_place(self.keys.offset(i));
+                    ptr::drop_in_place(self.values.offset(i));
+                }
+            }
+
+            self.alloc.deallocate(NonNull::new(self.keys as *mut u8).unwrap(), self.key_layout);
+            self.alloc.deallocate(NonNull::new(self.values as *mut u8).unwrap(), self.val_layout);
+            self.alloc.deallocate(NonNull::new(self.init_mask).unwrap(), self.mask_layout);
+        }
+    }
+}
+
+impl<K: PartialEq, V, A: Allocator> CIndexMap<K, V, A> {
     /// Gets the value associated with the specified key.
     ///
     /// Multiple implementations exist for `get`:
@@ -269,10 +448,13 @@ impl<K: PartialEq, V> CIndexMap<K, V> {
     ///   the expected results.
     pub fn get(&self, key: K) -> Option<&V> {
         // SAFETY:
-        // We know that all elements from (self.keys + 0) to (self.keys + self.pos) are initialized.
-        // Thus, reading from memory for each allocation of size_of::<K> is correct.
+        // We only read slots between 0 and self.pos that `init_mask` marks as initialized.
         unsafe {
             for i in 0..(self.pos + 1) {
+                if !get_bit(self.init_mask, i as usize) {
+                    continue;
+                }
+
                 if *self.keys.add(i as usize) == key {
                     return Some(&*self.values.add(i as usize));
                 }
@@ -288,9 +470,13 @@ impl<K: PartialEq, V> CIndexMap<K, V> {
     /// - If it does not, this function returns `false`.
     pub fn contains_key(&self, key: K) -> bool {
         // SAFETY:
-        // We only iterate over the data between 0 and self.pos, which is always initialized.
+        // We only read slots between 0 and self.pos that `init_mask` marks as initialized.
         unsafe {
             for i in 0..(self.pos + 1) {
+                if !get_bit(self.init_mask, i as usize) {
+                    continue;
+                }
+
                 if *self.keys.add(i as usize) == key {
                     return true;
                 }
@@ -301,16 +487,20 @@ impl<K: PartialEq, V> CIndexMap<K, V> {
     }
 }
 
-impl<K, V: PartialEq> CIndexMap<K, V> {
+impl<K, V: PartialEq, A: Allocator> CIndexMap<K, V, A> {
     /// Checks if the map contains the provided value.
     ///
     /// - If it does, this function returns `true`,
     /// - If it does not, this function returns `false`.
     pub fn contains_value(&self, value: V) -> bool {
         // SAFETY:
-        // We only iterate over the data between 0 and self.pos, which is always initialized.
+        // We only read slots between 0 and self.pos that `init_mask` marks as initialized.
         unsafe {
             for i in 0..(self.pos + 1) {
+                if !get_bit(self.init_mask, i as usize) {
+                    continue;
+                }
+
                 if *self.values.add(i as usize) == value {
                     return true;
                 }
@@ -319,4 +509,892 @@ impl<K, V: PartialEq> CIndexMap<K, V> {
 
         false
     }
+}
+
+/// An opaque, `Copy`-able handle returned by `HandleMap::insert`.
+///
+/// A `Handle` packs a slot index and a generation counter into a single `u64`, so it can be
+/// stored, copied, and passed across an FFI boundary without carrying a Rust lifetime. Redeeming
+/// a handle after its slot has been removed and reused returns `None` instead of touching the
+/// wrong value, which is what makes this safe to hand out in place of a raw pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle(u64);
+
+impl Handle {
+    fn new(index: u32, generation: u32) -> Handle {
+        Handle(((index as u64) << 32) | generation as u64)
+    }
+
+    fn index(&self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    fn generation(&self) -> u32 {
+        (self.0 & 0xFFFF_FFFF) as u32
+    }
+
+    /// Returns the raw `u64` representation of this handle.
+    ///
+    /// This is the value that's safe to pass across an FFI boundary; reconstruct the handle on
+    /// the other side with `Handle::from_u64`.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// Reconstructs a `Handle` from its raw `u64` representation.
+    pub fn from_u64(value: u64) -> Handle {
+        Handle(value)
+    }
+}
+
+/// A single slot in a `HandleMap`'s backing store.
+///
+/// `Vacant` slots form an intrusive singly-linked free list via `next_free`, so the map can
+/// find the next reusable slot without a separate allocation.
+#[derive(Debug)]
+enum Slot<V> {
+    Occupied { generation: u32, value: V },
+    Vacant { generation: u32, next_free: Option<u32> },
+}
+
+/// A generational-index map, handing out opaque `u64` handles instead of keys.
+///
+/// Unlike `CIndexMap`, `HandleMap` never compares keys to find a value: `insert` returns a
+/// `Handle` that can be redeemed in O(1) via `get`/`get_mut`/`remove`. Removed slots are reused
+/// by later inserts, but each slot tracks a generation counter so a handle from before a slot was
+/// removed can never be mistaken for a handle to whatever now occupies that slot. This makes
+/// `HandleMap` a good fit for storing Rust objects behind a stable integer handed across an FFI
+/// boundary, where a dangling pointer would otherwise be a risk.
+#[derive(Debug)]
+pub struct HandleMap<V> {
+    slots: Vec<Slot<V>>,
+
+    /// Index of the first vacant slot in the free list, if any.
+    free_head: Option<u32>,
+
+    /// The number of currently occupied slots.
+    len: usize,
+}
+
+impl<V> HandleMap<V> {
+    /// Constructs a new, empty `HandleMap`.
+    pub fn new() -> HandleMap<V> {
+        HandleMap {
+            slots: Vec::new(),
+            free_head: None,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of values currently stored in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts a value into the map, returning a `Handle` that can later be used to retrieve it.
+    pub fn insert(&mut self, value: V) -> Handle {
+        self.len += 1;
+
+        match self.free_head {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+
+                let generation = match slot {
+                    Slot::Vacant { generation, next_free } => {
+                        self.free_head = *next_free;
+                        *generation
+                    }
+                    Slot::Occupied { .. } => unreachable!("free list pointed at an occupied slot"),
+                };
+
+                *slot = Slot::Occupied { generation, value };
+                Handle::new(index, generation)
+            }
+            None => {
+                let index = self.slots.len() as u32;
+                self.slots.push(Slot::Occupied { generation: 1, value });
+                Handle::new(index, 1)
+            }
+        }
+    }
+
+    /// Returns a reference to the value behind `handle`, or `None` if the handle is stale or out
+    /// of range.
+    pub fn get(&self, handle: Handle) -> Option<&V> {
+        match self.slots.get(handle.index() as usize) {
+            Some(Slot::Occupied { generation, value }) if *generation == handle.generation() => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value behind `handle`, or `None` if the handle is stale
+    /// or out of range.
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut V> {
+        match self.slots.get_mut(handle.index() as usize) {
+            Some(Slot::Occupied { generation, value }) if *generation == handle.generation() => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks if `handle` currently refers to a live value in the map.
+    pub fn contains_handle(&self, handle: Handle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Removes and returns the value behind `handle`, or `None` if the handle is stale or out of
+    /// range.
+    ///
+    /// The slot's generation is incremented so that any copy of `handle` kept around after this
+    /// call will no longer resolve to whatever later reuses this slot.
+    pub fn remove(&mut self, handle: Handle) -> Option<V> {
+        let index = handle.index() as usize;
+
+        let matches = match self.slots.get(index) {
+            Some(Slot::Occupied { generation, .. }) => *generation == handle.generation(),
+            _ => false,
+        };
+
+        if !matches {
+            return None;
+        }
+
+        let next_generation = handle.generation().wrapping_add(1);
+        let next_free = if next_generation == 0 {
+            // The generation counter wrapped back to the sentinel value; retire this slot
+            // instead of putting it back in the free list, so it can never be reused.
+            None
+        } else {
+            self.free_head
+        };
+
+        let old = std::mem::replace(
+            &mut self.slots[index],
+            Slot::Vacant { generation: next_generation, next_free },
+        );
+
+        if next_generation != 0 {
+            self.free_head = Some(index as u32);
+        }
+
+        self.len -= 1;
+
+        match old {
+            Slot::Occupied { value, .. } => Some(value),
+            Slot::Vacant { .. } => unreachable!("checked above that the slot was occupied"),
+        }
+    }
+}
+
+impl<V> Default for HandleMap<V> {
+    fn default() -> Self {
+        HandleMap::new()
+    }
+}
+
+/// The state of a single bucket in a `CHashMap`'s table.
+///
+/// `Tombstone` marks a bucket that used to hold a key/value pair but was removed; it must keep
+/// participating in probe chains so that later lookups can still walk past it to whatever comes
+/// after, but it's free for a later `insert` to reclaim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum BucketState {
+    Empty = 0,
+    Occupied = 1,
+    Tombstone = 2,
+}
+
+/// The load factor ((live entries + tombstones) / capacity) above which `CHashMap` doubles its
+/// table.
+const HIGH_WATER_LOAD_FACTOR: f64 = 0.9;
+
+/// The load factor below which `CHashMap` halves its table on removal.
+const LOW_WATER_LOAD_FACTOR: f64 = 0.35;
+
+/// The smallest capacity `CHashMap` will ever allocate.
+const MIN_CAPACITY: usize = 4;
+
+/// An open-addressing hash map using the same raw-pointer/`Layout` allocation style as
+/// `CIndexMap`, laid out as a power-of-two bucket table with linear probing.
+///
+/// Unlike `CIndexMap::get`/`contains_key`, which walk every stored key, `CHashMap` hashes the
+/// key and probes forward from its bucket, giving expected O(1) lookups instead of an O(n) scan.
+/// Removed buckets are marked with a tombstone so probe chains stay intact; the table is doubled
+/// once the load factor passes `HIGH_WATER_LOAD_FACTOR` and halved once it drops below
+/// `LOW_WATER_LOAD_FACTOR`, rehashing live entries either way.
+#[derive(Debug)]
+pub struct CHashMap<K, V, A: Allocator = Global> {
+    /// The layout used for the current `keys` allocation.
+    key_layout: Layout,
+
+    /// The layout used for the current `values` allocation.
+    val_layout: Layout,
+
+    /// The layout used for the current `states` allocation.
+    state_layout: Layout,
+
+    /// A pointer to the bucket table's key storage.
+    keys: *mut K,
+
+    /// A pointer to the bucket table's value storage, parallel to `keys`.
+    values: *mut V,
+
+    /// A pointer to the bucket table's per-bucket state tags, parallel to `keys`/`values`.
+    states: *mut BucketState,
+
+    /// The number of buckets currently allocated. Always a power of two.
+    cap: usize,
+
+    /// The number of buckets currently holding a live key/value pair.
+    len: usize,
+
+    /// The number of buckets currently marked `Tombstone`.
+    tombstones: usize,
+
+    /// The allocator backing this map's `keys`/`values`/`states` storage.
+    alloc: A,
+}
+
+impl<K: Hash + Eq, V> CHashMap<K, V, Global> {
+    /// Constructs a new `CHashMap` with a small initial bucket table, backed by the global
+    /// allocator.
+    #[cfg(feature = "std")]
+    pub fn new() -> CHashMap<K, V, Global> {
+        CHashMap::new_in(Global)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Hash + Eq, V> Default for CHashMap<K, V, Global> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq, V, A: Allocator> CHashMap<K, V, A> {
+    /// Constructs a new `CHashMap` with a small initial bucket table, backed by the given
+    /// allocator `alloc`.
+    pub fn new_in(alloc: A) -> CHashMap<K, V, A> {
+        let cap = MIN_CAPACITY;
+
+        let key_layout = Layout::array::<K>(cap).unwrap();
+        let val_layout = Layout::array::<V>(cap).unwrap();
+        let state_layout = Layout::array::<BucketState>(cap).unwrap();
+
+        // SAFETY:
+        // `key_layout`/`val_layout` are non-zero-size array layouts for a non-zero `cap`.
+        let keys = alloc.allocate(key_layout).expect("allocation failure").as_ptr() as *mut K;
+        // SAFETY: same reasoning as `keys` above.
+        let values = alloc.allocate(val_layout).expect("allocation failure").as_ptr() as *mut V;
+        // SAFETY:
+        // `BucketState::Empty` is `0`, so a zeroed allocation is a valid, fully-initialized
+        // table of `Empty` states.
+        let states = alloc.allocate_zeroed(state_layout).expect("allocation failure").as_ptr() as *mut BucketState;
+
+        CHashMap {
+            key_layout,
+            val_layout,
+            state_layout,
+            keys,
+            values,
+            states,
+            cap,
+            len: 0,
+            tombstones: 0,
+            alloc,
+        }
+    }
+
+    fn hash_of(key: &K) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the bucket index that probing for `key` should start at.
+    fn start_index(&self, key: &K) -> usize {
+        (Self::hash_of(key) as usize) & (self.cap - 1)
+    }
+
+    /// Probes from `start` for either a bucket holding `key`, or the first `Empty`/`Tombstone`
+    /// bucket that an insert of `key` should use. Returns `(index, found_existing)`.
+    fn probe(&self, key: &K, start: usize) -> (usize, bool) {
+        let mut index = start;
+        let mut first_tombstone: Option<usize> = None;
+
+        loop {
+            // SAFETY: `index` is always masked into `0..self.cap`, which is allocated.
+            let state = unsafe { *self.states.add(index) };
+
+            match state {
+                BucketState::Empty => {
+                    return (first_tombstone.unwrap_or(index), false);
+                }
+                BucketState::Tombstone => {
+                    if first_tombstone.is_none() {
+                        first_tombstone = Some(index);
+                    }
+                }
+                BucketState::Occupied => {
+                    // SAFETY: the state says this bucket holds an initialized key.
+                    let existing = unsafe { &*self.keys.add(index) };
+                    if existing == key {
+                        return (index, true);
+                    }
+                }
+            }
+
+            index = (index + 1) & (self.cap - 1);
+        }
+    }
+
+    /// Inserts a key/value pair into the map, overwriting any existing value for `key`.
+    pub fn insert(&mut self, key: K, value: V) -> crate::result::Result<()> {
+        let start = self.start_index(&key);
+        let (index, found) = self.probe(&key, start);
+
+        if found {
+            // SAFETY: `found` means `index` holds an initialized value for an equal key.
+            unsafe {
+                ptr::drop_in_place(self.values.add(index));
+                self.values.add(index).write(value);
+            }
+            return Ok(());
+        }
+
+        // SAFETY: `index` was chosen by `probe` as an `Empty` or `Tombstone` bucket.
+        unsafe {
+            if *self.states.add(index) == BucketState::Tombstone {
+                self.tombstones -= 1;
+            }
+            self.keys.add(index).write(key);
+            self.values.add(index).write(value);
+            *self.states.add(index) = BucketState::Occupied;
+        }
+        self.len += 1;
+
+        if (self.len + self.tombstones) as f64 / self.cap as f64 > HIGH_WATER_LOAD_FACTOR {
+            self.resize(self.cap * 2)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets a reference to the value associated with `key`, if present.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let start = self.start_index(key);
+        let (index, found) = self.probe(key, start);
+
+        if !found {
+            return None;
+        }
+
+        // SAFETY: `found` means `index` holds an initialized value.
+        unsafe { Some(&*self.values.add(index)) }
+    }
+
+    /// Checks if the map contains the provided key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the value associated with `key`, returning it if it was present.
+    ///
+    /// The bucket is left as a tombstone rather than `Empty` so that probe chains through it
+    /// stay intact for keys that hashed to an earlier bucket.
+    pub fn remove(&mut self, key: &K) -> crate::result::Result<Option<V>> {
+        let start = self.start_index(key);
+        let (index, found) = self.probe(key, start);
+
+        if !found {
+            return Ok(None);
+        }
+
+        // SAFETY: `found` means `index` holds an initialized key/value pair.
+        let value = unsafe {
+            ptr::drop_in_place(self.keys.add(index));
+            let value = self.values.add(index).read();
+            *self.states.add(index) = BucketState::Tombstone;
+            value
+        };
+
+        self.len -= 1;
+        self.tombstones += 1;
+
+        if self.cap > MIN_CAPACITY && (self.len as f64 / self.cap as f64) < LOW_WATER_LOAD_FACTOR {
+            let mut new_cap = self.cap / 2;
+            while new_cap > MIN_CAPACITY && (self.len as f64 / new_cap as f64) < LOW_WATER_LOAD_FACTOR {
+                new_cap /= 2;
+            }
+            self.resize(new_cap.max(MIN_CAPACITY))?;
+        }
+
+        Ok(Some(value))
+    }
+
+    /// Reallocates the bucket table at `new_cap` and rehashes every live entry into it.
+    ///
+    /// `new_cap` must be a power of two at least as large as `self.len`.
+    fn resize(&mut self, new_cap: usize) -> crate::result::Result<()> {
+        let new_key_layout = Layout::array::<K>(new_cap).unwrap();
+        let new_val_layout = Layout::array::<V>(new_cap).unwrap();
+        let new_state_layout = Layout::array::<BucketState>(new_cap).unwrap();
+
+        // SAFETY: the layouts above describe non-zero-size arrays of `new_cap` elements.
+        let new_keys = self.alloc.allocate(new_key_layout)
+            .map_err(|_| CIndexMapError::new(
+                MapErrorKind::AllocationError,
+                "Error when attempting to allocate map memory."
+            ))?
+            .as_ptr() as *mut K;
+        let new_values = self.alloc.allocate(new_val_layout)
+            .map_err(|_| CIndexMapError::new(
+                MapErrorKind::AllocationError,
+                "Error when attempting to allocate map memory."
+            ))?
+            .as_ptr() as *mut V;
+        // SAFETY: `BucketState::Empty` is `0`, so zeroing is a valid initial state.
+        let new_states = self.alloc.allocate_zeroed(new_state_layout)
+            .map_err(|_| CIndexMapError::new(
+                MapErrorKind::AllocationError,
+                "Error when attempting to allocate map memory."
+            ))?
+            .as_ptr() as *mut BucketState;
+
+        let old_keys = self.keys;
+        let old_values = self.values;
+        let old_states = self.states;
+        let old_cap = self.cap;
+
+        // Move every live entry into the new table by rehashing it against `new_cap`, since a
+        // table resize changes which bucket each key's hash maps to.
+        for i in 0..old_cap {
+            // SAFETY: `i` is in bounds of the old table.
+            unsafe {
+                if *old_states.add(i) == BucketState::Occupied {
+                    let key = old_keys.add(i).read();
+                    let value = old_values.add(i).read();
+
+                    let mut index = (Self::hash_of(&key) as usize) & (new_cap - 1);
+                    while *new_states.add(index) == BucketState::Occupied {
+                        index = (index + 1) & (new_cap - 1);
+                    }
+
+                    new_keys.add(index).write(key);
+                    new_values.add(index).write(value);
+                    *new_states.add(index) = BucketState::Occupied;
+                }
+            }
+        }
+
+        // SAFETY: `old_keys`/`old_values`/`old_states` were allocated with the layouts recorded
+        // in `self.key_layout`/`self.val_layout`/`self.state_layout`, and every live element was
+        // moved out above, so no destructor runs are skipped.
+        unsafe {
+            self.alloc.deallocate(NonNull::new(old_keys as *mut u8).unwrap(), self.key_layout);
+            self.alloc.deallocate(NonNull::new(old_values as *mut u8).unwrap(), self.val_layout);
+            self.alloc.deallocate(NonNull::new(old_states as *mut u8).unwrap(), self.state_layout);
+        }
+
+        self.keys = new_keys;
+        self.values = new_values;
+        self.states = new_states;
+        self.key_layout = new_key_layout;
+        self.val_layout = new_val_layout;
+        self.state_layout = new_state_layout;
+        self.cap = new_cap;
+        self.tombstones = 0;
+
+        Ok(())
+    }
+}
+
+impl<K, V, A: Allocator> Drop for CHashMap<K, V, A> {
+    fn drop(&mut self) {
+        // SAFETY:
+        // `BucketState::Occupied` guarantees the corresponding `keys`/`values` bucket is
+        // initialized; `Empty`/`Tombstone` buckets must not be dropped. `self.keys`/
+        // `self.values`/`self.states` were allocated with `self.key_layout`/`self.val_layout`/
+        // `self.state_layout`, which are kept in sync with the current capacity by `resize`, so
+        // deallocating with them here is sound.
+        unsafe {
+            for i in 0..self.cap {
+                if *self.states.add(i) == BucketState::Occupied {
+                    ptr::drop_in_place(self.keys.add(i));
+                    ptr::drop_in_place(self.values.add(i));
+                }
+            }
+
+            self.alloc.deallocate(NonNull::new(self.keys as *mut u8).unwrap(), self.key_layout);
+            self.alloc.deallocate(NonNull::new(self.values as *mut u8).unwrap(), self.val_layout);
+            self.alloc.deallocate(NonNull::new(self.states as *mut u8).unwrap(), self.state_layout);
+        }
+    }
+}
+
+/// Magic bytes every `PersistentIndexMap` file begins with.
+const PERSISTENT_MAGIC: [u8; 4] = *b"XMAP";
+
+/// The on-disk format version written by this version of `x-map`.
+const PERSISTENT_FORMAT_VERSION: u8 = 1;
+
+/// The size in bytes of a `PersistentIndexMap`'s fixed header: magic bytes, format version,
+/// three bytes of padding, then the entry count.
+const PERSISTENT_HEADER_SIZE: usize = 16;
+
+/// The byte layout of a `PersistentIndexMap`'s bucket region for a table of `cap` buckets: the
+/// offset its per-bucket state tags start at, the offset its keys start at, the offset its
+/// values start at, and the total file length required to hold all three.
+fn persistent_layout<K, V>(cap: usize) -> (usize, usize, usize, usize) {
+    let states_offset = PERSISTENT_HEADER_SIZE;
+    let keys_offset = round_up(states_offset + cap, align_of::<K>());
+    let values_offset = round_up(keys_offset + cap * size_of::<K>(), align_of::<V>());
+    let total_len = values_offset + cap * size_of::<V>();
+
+    (states_offset, keys_offset, values_offset, total_len)
+}
+
+fn round_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+/// A disk-backed, memory-mapped counterpart to `CHashMap`.
+///
+/// `PersistentIndexMap` memory-maps a file laid out as a fixed header (magic bytes, a format
+/// version, and the live entry count) followed by the same state/key/value bucket layout as
+/// `CHashMap`, so an index survives process restarts. `K` and `V` must be `Copy`: the file holds
+/// their bytes directly, so neither type may own out-of-line heap data that wouldn't survive
+/// being read back from disk in a later process.
+///
+/// Growth and shrink follow the same load-factor discipline as `CHashMap`: the table is doubled
+/// once occupancy passes `HIGH_WATER_LOAD_FACTOR` and halved once it falls under
+/// `LOW_WATER_LOAD_FACTOR`, re-growing and re-mapping the backing file either way.
+///
+/// `create`, `insert`, and `remove` all flush the mapping before returning, so a write is on
+/// disk by the time the call completes instead of depending on the OS to flush it back on an
+/// unclean exit.
+#[derive(Debug)]
+pub struct PersistentIndexMap<K: Copy, V: Copy> {
+    file: File,
+    mmap: MmapMut,
+    cap: usize,
+    len: usize,
+    tombstones: usize,
+    _marker: PhantomData<(K, V)>,
+}
+
+impl<K: Hash + Eq + Copy, V: Copy> PersistentIndexMap<K, V> {
+    /// Creates a new, empty `PersistentIndexMap` backed by the file at `path`, overwriting
+    /// whatever is there.
+    pub fn create(path: impl AsRef<Path>) -> crate::result::Result<PersistentIndexMap<K, V>> {
+        let cap = MIN_CAPACITY;
+        let (states_offset, _, _, total_len) = persistent_layout::<K, V>(cap);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+
+        file.set_len(total_len as u64)
+            .map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+
+        // SAFETY: `file` was just sized to `total_len` and isn't shared with another process.
+        let mut mmap = unsafe { MmapMut::map_mut(&file) }
+            .map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+
+        mmap[0..4].copy_from_slice(&PERSISTENT_MAGIC);
+        mmap[4] = PERSISTENT_FORMAT_VERSION;
+        mmap[8..16].copy_from_slice(&0u64.to_le_bytes());
+        mmap[states_offset..states_offset + cap].fill(BucketState::Empty as u8);
+
+        mmap.flush().map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+
+        Ok(PersistentIndexMap {
+            file,
+            mmap,
+            cap,
+            len: 0,
+            tombstones: 0,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Opens an existing `PersistentIndexMap` file, validating its header.
+    ///
+    /// Returns `MapErrorKind::WrongMagic` if the file doesn't start with the expected magic
+    /// bytes or its length matches no bucket table size, `MapErrorKind::UnsupportedVersion` if it was written by an incompatible version of
+    /// `x-map`, or `MapErrorKind::WrongEntryCount` if the header's recorded entry count doesn't
+    /// match the number of occupied buckets actually found in the file.
+    pub fn open(path: impl AsRef<Path>) -> crate::result::Result<PersistentIndexMap<K, V>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+
+        // SAFETY: `file` was opened above and isn't shared with another process.
+        let mmap = unsafe { MmapMut::map_mut(&file) }
+            .map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+
+        if mmap.len() < PERSISTENT_HEADER_SIZE || mmap[0..4] != PERSISTENT_MAGIC {
+            return Err(CIndexMapError::new(
+                MapErrorKind::WrongMagic,
+                "Persistent map file did not start with the expected magic bytes.",
+            ));
+        }
+
+        let version = mmap[4];
+        if version != PERSISTENT_FORMAT_VERSION {
+            return Err(CIndexMapError::new(
+                MapErrorKind::UnsupportedVersion(version),
+                format!("Persistent map file has unsupported format version {version}."),
+            ));
+        }
+
+        let header_count = u64::from_le_bytes(mmap[8..16].try_into().unwrap());
+
+        // Recover `cap` from the file length, since the header doesn't store it directly: it's
+        // whatever capacity makes `persistent_layout`'s total length match the file on disk.
+        let mut cap = MIN_CAPACITY;
+        while persistent_layout::<K, V>(cap).3 != mmap.len() {
+            cap *= 2;
+            if cap > (1 << 32) {
+                return Err(CIndexMapError::new(
+                    MapErrorKind::WrongMagic,
+                    "Persistent map file length does not match any valid bucket table size.",
+                ));
+            }
+        }
+
+        let (states_offset, _, _, _) = persistent_layout::<K, V>(cap);
+        let mut actual_count = 0u64;
+        let mut tombstones = 0usize;
+        for i in 0..cap {
+            match mmap[states_offset + i] {
+                x if x == BucketState::Occupied as u8 => actual_count += 1,
+                x if x == BucketState::Tombstone as u8 => tombstones += 1,
+                _ => {}
+            }
+        }
+
+        if actual_count != header_count {
+            return Err(CIndexMapError::new(
+                MapErrorKind::WrongEntryCount { header: header_count, actual: actual_count },
+                format!(
+                    "Persistent map header recorded {header_count} entries, but {actual_count} were found."
+                ),
+            ));
+        }
+
+        Ok(PersistentIndexMap {
+            file,
+            mmap,
+            cap,
+            len: actual_count as usize,
+            tombstones,
+            _marker: PhantomData,
+        })
+    }
+
+    fn offsets(&self) -> (usize, usize, usize) {
+        let (states_offset, keys_offset, values_offset, _) = persistent_layout::<K, V>(self.cap);
+        (states_offset, keys_offset, values_offset)
+    }
+
+    fn hash_of(key: &K) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Probes from the bucket `key` hashes to for either a bucket holding `key`, or the first
+    /// `Empty`/`Tombstone` bucket that an insert of `key` should use. Returns
+    /// `(index, found_existing)`.
+    fn probe(&self, key: &K) -> (usize, bool) {
+        let (states_offset, keys_offset, _) = self.offsets();
+        let mut index = (Self::hash_of(key) as usize) & (self.cap - 1);
+        let mut first_tombstone: Option<usize> = None;
+
+        loop {
+            let state = self.mmap[states_offset + index];
+
+            if state == BucketState::Empty as u8 {
+                return (first_tombstone.unwrap_or(index), false);
+            } else if state == BucketState::Tombstone as u8 {
+                if first_tombstone.is_none() {
+                    first_tombstone = Some(index);
+                }
+            } else {
+                // SAFETY: `keys_offset + index * size_of::<K>()` is in bounds of the mapped
+                // region, and `Occupied` means that slot holds an initialized `K`.
+                let existing = unsafe {
+                    &*(self.mmap.as_ptr().add(keys_offset + index * size_of::<K>()) as *const K)
+                };
+                if existing == key {
+                    return (index, true);
+                }
+            }
+
+            index = (index + 1) & (self.cap - 1);
+        }
+    }
+
+    fn write_entry_count(&mut self) {
+        self.mmap[8..16].copy_from_slice(&(self.len as u64).to_le_bytes());
+    }
+
+    /// Flushes pending writes to the backing file, so an unclean process exit doesn't leave
+    /// them sitting only in the OS page cache.
+    fn flush(&self) -> crate::result::Result<()> {
+        self.mmap.flush().map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))
+    }
+
+    /// Inserts a key/value pair into the map, overwriting any existing value for `key`.
+    pub fn insert(&mut self, key: K, value: V) -> crate::result::Result<()> {
+        let (index, found) = self.probe(&key);
+        let (states_offset, keys_offset, values_offset) = self.offsets();
+
+        if found {
+            // SAFETY: `found` means `index` holds an initialized value for an equal key.
+            unsafe {
+                (self.mmap.as_mut_ptr().add(values_offset + index * size_of::<V>()) as *mut V)
+                    .write(value);
+            }
+            return self.flush();
+        }
+
+        if self.mmap[states_offset + index] == BucketState::Tombstone as u8 {
+            self.tombstones -= 1;
+        }
+
+        // SAFETY: `index` was chosen by `probe` as an `Empty` or `Tombstone` bucket, and both
+        // offsets are in bounds of the mapped region.
+        unsafe {
+            (self.mmap.as_mut_ptr().add(keys_offset + index * size_of::<K>()) as *mut K).write(key);
+            (self.mmap.as_mut_ptr().add(values_offset + index * size_of::<V>()) as *mut V)
+                .write(value);
+        }
+        self.mmap[states_offset + index] = BucketState::Occupied as u8;
+        self.len += 1;
+        self.write_entry_count();
+
+        if (self.len + self.tombstones) as f64 / self.cap as f64 > HIGH_WATER_LOAD_FACTOR {
+            self.resize(self.cap * 2)?;
+        }
+
+        self.flush()
+    }
+
+    /// Gets a reference to the value associated with `key`, if present.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let (index, found) = self.probe(key);
+        if !found {
+            return None;
+        }
+
+        let (_, _, values_offset) = self.offsets();
+        // SAFETY: `found` means `index` holds an initialized value.
+        unsafe {
+            Some(&*(self.mmap.as_ptr().add(values_offset + index * size_of::<V>()) as *const V))
+        }
+    }
+
+    /// Removes the value associated with `key`, returning it if it was present.
+    pub fn remove(&mut self, key: &K) -> crate::result::Result<Option<V>> {
+        let (index, found) = self.probe(key);
+        if !found {
+            return Ok(None);
+        }
+
+        let (states_offset, _, values_offset) = self.offsets();
+        // SAFETY: `found` means `index` holds an initialized value.
+        let value = unsafe {
+            *(self.mmap.as_ptr().add(values_offset + index * size_of::<V>()) as *const V)
+        };
+        self.mmap[states_offset + index] = BucketState::Tombstone as u8;
+
+        self.len -= 1;
+        self.tombstones += 1;
+        self.write_entry_count();
+
+        if self.cap > MIN_CAPACITY && (self.len as f64 / self.cap as f64) < LOW_WATER_LOAD_FACTOR {
+            let mut new_cap = self.cap / 2;
+            while new_cap > MIN_CAPACITY && (self.len as f64 / new_cap as f64) < LOW_WATER_LOAD_FACTOR {
+                new_cap /= 2;
+            }
+            self.resize(new_cap.max(MIN_CAPACITY))?;
+        }
+
+        self.flush()?;
+        Ok(Some(value))
+    }
+
+    /// Re-grows or re-maps the backing file at `new_cap` and rehashes every live entry into it.
+    fn resize(&mut self, new_cap: usize) -> crate::result::Result<()> {
+        let (old_states_offset, old_keys_offset, old_values_offset, _) =
+            persistent_layout::<K, V>(self.cap);
+        let (new_states_offset, new_keys_offset, new_values_offset, new_total_len) =
+            persistent_layout::<K, V>(new_cap);
+
+        let mut new_bytes = vec![0u8; new_total_len];
+        new_bytes[0..4].copy_from_slice(&PERSISTENT_MAGIC);
+        new_bytes[4] = PERSISTENT_FORMAT_VERSION;
+        new_bytes[new_states_offset..new_states_offset + new_cap].fill(BucketState::Empty as u8);
+
+        for i in 0..self.cap {
+            if self.mmap[old_states_offset + i] == BucketState::Occupied as u8 {
+                // SAFETY: `Occupied` means this bucket holds an initialized key/value pair,
+                // and both offsets are in bounds of the currently mapped region.
+                let (key, value) = unsafe {
+                    let key = *(self.mmap.as_ptr().add(old_keys_offset + i * size_of::<K>()) as *const K);
+                    let value =
+                        *(self.mmap.as_ptr().add(old_values_offset + i * size_of::<V>()) as *const V);
+                    (key, value)
+                };
+
+                let mut index = (Self::hash_of(&key) as usize) & (new_cap - 1);
+                while new_bytes[new_states_offset + index] == BucketState::Occupied as u8 {
+                    index = (index + 1) & (new_cap - 1);
+                }
+
+                // SAFETY: `index` is in bounds of `new_bytes`, which was sized for `new_cap`.
+                unsafe {
+                    (new_bytes.as_mut_ptr().add(new_keys_offset + index * size_of::<K>()) as *mut K)
+                        .write(key);
+                    (new_bytes.as_mut_ptr().add(new_values_offset + index * size_of::<V>()) as *mut V)
+                        .write(value);
+                }
+                new_bytes[new_states_offset + index] = BucketState::Occupied as u8;
+            }
+        }
+
+        new_bytes[8..16].copy_from_slice(&(self.len as u64).to_le_bytes());
+
+        self.file
+            .set_len(new_total_len as u64)
+            .map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+
+        // SAFETY: `self.file` was just resized to `new_total_len`.
+        let mut new_mmap = unsafe { MmapMut::map_mut(&self.file) }
+            .map_err(|e| CIndexMapError::new(MapErrorKind::AllocationError, e.to_string()))?;
+        new_mmap.copy_from_slice(&new_bytes);
+
+        self.mmap = new_mmap;
+        self.cap = new_cap;
+        self.tombstones = 0;
+
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -1,4 +1,32 @@
-use crate::maps::CIndexMap;
+use crate::maps::{CHashMap, CIndexMap, HandleMap};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// A value that records into a shared counter when it's dropped, so a test can assert a map
+/// actually ran destructors instead of leaking.
+struct DropCounter(Rc<Cell<i32>>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn test_c_index_map_drop_runs_element_destructors() {
+    let count = Rc::new(Cell::new(0));
+    let mut map: CIndexMap<i32, DropCounter> = CIndexMap::new();
+
+    for i in 0..10 {
+        map.insert(i, DropCounter(count.clone())).unwrap();
+    }
+    // Removing one also has to drop the element it removes, not just the ones left in the map.
+    map.remove(0).unwrap();
+    assert_eq!(count.get(), 1);
+
+    drop(map);
+    assert_eq!(count.get(), 10);
+}
 
 #[test]
 fn test_insert() {
@@ -6,12 +34,133 @@ fn test_insert() {
     let string_two = String::from("bar");
     let mut c_index: CIndexMap<String, String> = CIndexMap::new();
 
-    c_index.insert(string_one.to_string(), string_two.to_string());
-    c_index.insert(string_two.to_string(), string_one.to_string());
+    c_index.insert(string_one.to_string(), string_two.to_string()).unwrap();
+    c_index.insert(string_two.to_string(), string_one.to_string()).unwrap();
 
-    dbg!(c_index.index(0));
-    dbg!(c_index.index(1));
+    let _ = dbg!(c_index.index(0));
+    let _ = dbg!(c_index.index(1));
     c_index.remove(1).unwrap();
     dbg!(c_index.index(0).unwrap());
-    dbg!(c_index.index(1));
+    let _ = dbg!(c_index.index(1));
+}
+
+#[test]
+fn test_handle_map_len_and_is_empty() {
+    let mut map: HandleMap<i32> = HandleMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.len(), 0);
+
+    let handle = map.insert(1);
+    assert!(!map.is_empty());
+    assert_eq!(map.len(), 1);
+
+    map.remove(handle);
+    assert!(map.is_empty());
+    assert_eq!(map.len(), 0);
+}
+
+#[test]
+fn test_handle_map_stale_handle_rejected_after_reinsert() {
+    let mut map: HandleMap<i32> = HandleMap::new();
+
+    let first = map.insert(1);
+    assert_eq!(map.get(first), Some(&1));
+
+    map.remove(first).unwrap();
+    assert_eq!(map.get(first), None);
+
+    // The freed slot gets reused, but with a bumped generation - the old handle must still
+    // be rejected even though it points at the same index again.
+    let second = map.insert(2);
+    assert_eq!(second.as_u64() >> 32, first.as_u64() >> 32);
+    assert_eq!(second.as_u64() & 0xFFFF_FFFF, (first.as_u64() & 0xFFFF_FFFF) + 1);
+    assert_eq!(map.get(first), None);
+    assert_eq!(map.get(second), Some(&2));
+    assert!(!map.contains_handle(first));
+    assert!(map.contains_handle(second));
+}
+
+#[test]
+fn test_chash_map_insert_get_overwrite() {
+    let mut map: CHashMap<String, i32> = CHashMap::new();
+    map.insert("foo".to_string(), 1).unwrap();
+    map.insert("bar".to_string(), 2).unwrap();
+
+    assert_eq!(map.get(&"foo".to_string()), Some(&1));
+    assert_eq!(map.get(&"bar".to_string()), Some(&2));
+    assert_eq!(map.get(&"baz".to_string()), None);
+
+    // Inserting an existing key overwrites its value in place rather than adding a bucket.
+    map.insert("foo".to_string(), 10).unwrap();
+    assert_eq!(map.get(&"foo".to_string()), Some(&10));
+}
+
+#[test]
+fn test_chash_map_tombstone_reuse_after_remove() {
+    let mut map: CHashMap<i32, i32> = CHashMap::new();
+    map.insert(1, 1).unwrap();
+    map.insert(2, 2).unwrap();
+
+    assert_eq!(map.remove(&1).unwrap(), Some(1));
+    assert_eq!(map.get(&1), None);
+    // `2` must still be reachable: removing `1` left a tombstone rather than breaking the
+    // probe chain that later keys may rely on.
+    assert_eq!(map.get(&2), Some(&2));
+
+    // Re-inserting a removed key should reuse the tombstoned bucket rather than probing past it.
+    map.insert(1, 100).unwrap();
+    assert_eq!(map.get(&1), Some(&100));
+}
+
+#[test]
+fn test_chash_map_grows_and_shrinks_across_load_factor_thresholds() {
+    let mut map: CHashMap<i32, i32> = CHashMap::new();
+
+    for i in 0..64 {
+        map.insert(i, i * 10).unwrap();
+    }
+    for i in 0..64 {
+        assert_eq!(map.get(&i), Some(&(i * 10)));
+    }
+
+    for i in 0..60 {
+        assert_eq!(map.remove(&i).unwrap(), Some(i * 10));
+    }
+    for i in 0..60 {
+        assert_eq!(map.get(&i), None);
+    }
+    for i in 60..64 {
+        assert_eq!(map.get(&i), Some(&(i * 10)));
+    }
+}
+
+#[test]
+fn test_c_index_map_reserve_and_insert_at_leave_sparse_holes() {
+    let mut map: CIndexMap<String, i32> = CIndexMap::new();
+    map.reserve(5).unwrap();
+
+    // Fill only the odd slots, leaving 0, 2, 4 as uninitialized holes.
+    map.insert_at(1, "one".to_string(), 1).unwrap();
+    map.insert_at(3, "three".to_string(), 3).unwrap();
+
+    assert_eq!(map.get("one".to_string()), Some(&1));
+    assert_eq!(map.get("three".to_string()), Some(&3));
+}
+
+#[test]
+fn test_c_index_map_remove_shifts_past_sparse_holes() {
+    let mut map: CIndexMap<String, i32> = CIndexMap::new();
+    map.reserve(5).unwrap();
+
+    // Slot 1 is a hole; removing slot 0 has to shift slots 1..=3 down without reading
+    // the uninitialized hole at 1 as a `String`.
+    map.insert_at(0, "zero".to_string(), 0).unwrap();
+    map.insert_at(2, "two".to_string(), 2).unwrap();
+    map.insert_at(3, "three".to_string(), 3).unwrap();
+
+    map.remove(0).unwrap();
+
+    assert_eq!(map.get("two".to_string()), Some(&2));
+    assert_eq!(map.get("three".to_string()), Some(&3));
+    assert_eq!(map.get("zero".to_string()), None);
 }
\ No newline at end of file
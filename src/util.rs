@@ -1,24 +1,33 @@
 use std::ptr;
 
-/// Returns a `usize` corresponding to the size of a struct time the amount of space for those
-/// structs to be allocated.
-///
-/// This is useful for when we want to get an accurate memory size to allocate for a map.
-pub(in crate) fn new_capacity_of<T>(size: usize) -> usize {
-    size_of::<T>() * size
-}
-
 /// Compares the memory in two pointers.
 ///
 /// The index at which the two pointers' underlying data no longer match is returned. This will
 /// return `None` if the two structures are identical.
 pub(in crate) unsafe fn mem_cmp(left: *const u8, right: *const u8, size: usize) -> Option<usize> {
     // Compare each pointer byte-by-byte.
-    for i in 0..size {
-        if ptr::read(left.add(i)) != ptr::read(right.add(i)) {
-            return Some(i);
-        }
-    }
+    (0..size).find(|&i| ptr::read(left.add(i)) != ptr::read(right.add(i)))
+}
+
+/// Returns the number of bytes needed to pack one bit per slot for `slots` slots.
+pub(in crate) fn mask_bytes(slots: usize) -> usize {
+    slots.div_ceil(8)
+}
+
+/// Marks `index` as initialized in the packed bitmask pointed to by `mask`.
+pub(in crate) unsafe fn set_bit(mask: *mut u8, index: usize) {
+    let byte = mask.add(index / 8);
+    *byte |= 1 << (index % 8);
+}
+
+/// Marks `index` as uninitialized in the packed bitmask pointed to by `mask`.
+pub(in crate) unsafe fn clear_bit(mask: *mut u8, index: usize) {
+    let byte = mask.add(index / 8);
+    *byte &= !(1 << (index % 8));
+}
 
-    None
+/// Returns whether `index` is marked as initialized in the packed bitmask pointed to by `mask`.
+pub(in crate) unsafe fn get_bit(mask: *const u8, index: usize) -> bool {
+    let byte = *mask.add(index / 8);
+    (byte >> (index % 8)) & 1 == 1
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use x_map::error::MapErrorKind;
+use x_map::maps::PersistentIndexMap;
+use std::io::Write;
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("xmap-persistent-{name}-{}.bin", std::process::id()))
+}
+
+#[test]
+fn test_persistent_round_trip_through_reopen() {
+    let path = temp_path("round-trip");
+    let _ = std::fs::remove_file(&path);
+
+    {
+        let mut map: PersistentIndexMap<i32, i32> = PersistentIndexMap::create(&path).unwrap();
+        map.insert(1, 100).unwrap();
+        map.insert(2, 200).unwrap();
+        assert_eq!(map.get(&1), Some(&100));
+    }
+
+    {
+        let mut map: PersistentIndexMap<i32, i32> = PersistentIndexMap::open(&path).unwrap();
+        assert_eq!(map.get(&1), Some(&100));
+        assert_eq!(map.get(&2), Some(&200));
+
+        assert_eq!(map.remove(&1).unwrap(), Some(100));
+        assert_eq!(map.get(&1), None);
+    }
+
+    {
+        let map: PersistentIndexMap<i32, i32> = PersistentIndexMap::open(&path).unwrap();
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&2), Some(&200));
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_persistent_grow_and_shrink_across_reopen() {
+    let path = temp_path("grow-shrink");
+    let _ = std::fs::remove_file(&path);
+
+    {
+        let mut map: PersistentIndexMap<i32, i32> = PersistentIndexMap::create(&path).unwrap();
+        for i in 0..64 {
+            map.insert(i, i * 10).unwrap();
+        }
+    }
+
+    {
+        let mut map: PersistentIndexMap<i32, i32> = PersistentIndexMap::open(&path).unwrap();
+        for i in 0..64 {
+            assert_eq!(map.get(&i), Some(&(i * 10)));
+        }
+
+        for i in 0..60 {
+            assert_eq!(map.remove(&i).unwrap(), Some(i * 10));
+        }
+    }
+
+    {
+        let map: PersistentIndexMap<i32, i32> = PersistentIndexMap::open(&path).unwrap();
+        for i in 0..60 {
+            assert_eq!(map.get(&i), None);
+        }
+        for i in 60..64 {
+            assert_eq!(map.get(&i), Some(&(i * 10)));
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_persistent_open_rejects_wrong_magic() {
+    let path = temp_path("wrong-magic");
+    let _ = std::fs::remove_file(&path);
+
+    std::fs::write(&path, [0u8; 64]).unwrap();
+
+    let err = PersistentIndexMap::<i32, i32>::open(&path).unwrap_err();
+    assert!(matches!(err.kind(), MapErrorKind::WrongMagic));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_persistent_open_rejects_unsupported_version() {
+    let path = temp_path("wrong-version");
+    let _ = std::fs::remove_file(&path);
+
+    {
+        let _map: PersistentIndexMap<i32, i32> = PersistentIndexMap::create(&path).unwrap();
+    }
+
+    // Overwrite just the version byte (offset 4) with an unsupported value, leaving the magic intact.
+    {
+        use std::io::{Seek, SeekFrom};
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(4)).unwrap();
+        file.write_all(&[255]).unwrap();
+    }
+
+    let err = PersistentIndexMap::<i32, i32>::open(&path).unwrap_err();
+    assert!(matches!(err.kind(), MapErrorKind::UnsupportedVersion(255)));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_persistent_open_rejects_wrong_entry_count() {
+    let path = temp_path("wrong-entry-count");
+    let _ = std::fs::remove_file(&path);
+
+    {
+        let mut map: PersistentIndexMap<i32, i32> = PersistentIndexMap::create(&path).unwrap();
+        map.insert(1, 100).unwrap();
+    }
+
+    // Corrupt the header's recorded entry count so it no longer matches the one occupied
+    // bucket actually present in the file.
+    {
+        use std::io::{Seek, SeekFrom};
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(8)).unwrap();
+        file.write_all(&99u64.to_le_bytes()).unwrap();
+    }
+
+    let err = PersistentIndexMap::<i32, i32>::open(&path).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        MapErrorKind::WrongEntryCount { header: 99, actual: 1 }
+    ));
+
+    let _ = std::fs::remove_file(&path);
+}